@@ -1,21 +1,27 @@
-use sha1::{Digest, Sha1};
+use crate::hash::{to_hex, HashAlgo, HashMode};
+use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufReader, Read};
-use std::path::Path;
+use std::io::{BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
 
-/// Calculates the SHA1 hash of a file located at the given file path.
+/// Size, in bytes, of the prefix block read by [`partial_hash`].
+pub const PARTIAL_HASH_BLOCK_SIZE: usize = 4096;
+
+/// Calculates the content hash of a file located at the given file path, using `algo`.
 ///
 /// # Arguments
 ///
 /// * `file_path` - A `PathBuf` representing the path to the file to be hashed.
+/// * `algo` - The hash algorithm to use.
 ///
 /// # Returns
 ///
-/// * `Result<String, std::io::Error>` - A `Result` containing the SHA1 hash of the file as a `String` if successful, or an `std::io::Error` if an error occurred while reading the file.
-pub fn calculate_sha1<P: AsRef<Path>>(file_path: P) -> Result<String, std::io::Error> {
+/// * `Result<String, std::io::Error>` - A `Result` containing the hex-encoded hash of the file if successful, or an `std::io::Error` if an error occurred while reading the file.
+pub fn calculate_hash<P: AsRef<Path>>(file_path: P, algo: HashAlgo) -> Result<String, std::io::Error> {
     let file = File::open(file_path)?;
     let mut reader = BufReader::new(file);
-    let mut hasher = Sha1::new();
+    let mut hasher = algo.hasher();
     let mut buffer = [0; 1024];
     loop {
         let bytes_read = reader.read(&mut buffer)?;
@@ -24,33 +30,112 @@ pub fn calculate_sha1<P: AsRef<Path>>(file_path: P) -> Result<String, std::io::E
         }
         hasher.update(&buffer[..bytes_read]);
     }
-    let hash = hasher.finalize();
-    Ok(format!("{:x}", hash))
+    Ok(to_hex(&hasher.finalize()))
 }
 
-/// Copies a file located at the given file path to the specified output directory, if it does not already exist there.
+/// Digests at most the first [`PARTIAL_HASH_BLOCK_SIZE`] bytes of a file, using `algo`.
+///
+/// When the file is no larger than [`PARTIAL_HASH_BLOCK_SIZE`], the read covers its entire
+/// content, so the returned hash already *is* the file's true content hash and the mode is
+/// [`HashMode::Full`] - safe to use directly as a blob's oid. Otherwise only a prefix was read
+/// (the length is mixed in so two differently-sized files with the same prefix don't collide),
+/// the mode is [`HashMode::Partial`], and callers must fall back to [`calculate_hash`] to get a
+/// trustworthy content oid for the whole file.
+pub fn partial_hash<P: AsRef<Path>>(file_path: P, algo: HashAlgo) -> Result<(String, HashMode), std::io::Error> {
+    let file = File::open(file_path)?;
+    let len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut buffer = [0u8; PARTIAL_HASH_BLOCK_SIZE];
+    let mut read_total = 0;
+    while read_total < buffer.len() {
+        let bytes_read = reader.read(&mut buffer[read_total..])?;
+        if bytes_read == 0 {
+            break;
+        }
+        read_total += bytes_read;
+    }
+
+    let mut hasher = algo.hasher();
+    hasher.update(&buffer[..read_total]);
+
+    // The buffer filled up; probe for one more byte to tell "file is exactly one block long"
+    // (fully covered) apart from "there's more file left to read" (truly partial).
+    let mut probe = [0u8; 1];
+    let truncated = read_total == buffer.len() && reader.read(&mut probe)? > 0;
+    let mode = if truncated {
+        hasher.update(&len.to_be_bytes());
+        HashMode::Partial
+    } else {
+        HashMode::Full
+    };
+    Ok((to_hex(&hasher.finalize()), mode))
+}
+
+/// Copies a file located at the given file path into the content-addressable store, if it does
+/// not already exist there. Returns the blob's content oid.
+///
+/// The stored object is prefixed with a 1-byte header identifying `algo`, so a reader can pick
+/// the right verifier when it later loads the object back from disk.
 ///
 /// # Arguments
 ///
 /// * `from` - A `PathBuf` representing the path to the file to be copied.
 /// * `to` - A `PathBuf` representing the directory to which the file should be copied.
-///
-/// # Returns
-///
-/// * `Result<(), std::io::Error>` - A `Result` containing `()` if the file was successfully copied or already exists in the output directory, or an `std::io::Error` if an error occurred while copying the file or creating the necessary directories.
-pub fn write_file_blob<P1: AsRef<Path>, P2: AsRef<Path>>(from: P1, to: P2) -> Result<(), std::io::Error> {
-    let hash = calculate_sha1(&from)?;
-    let subfolder = &hash[..2];
-    let file_name = &hash[2..];
+/// * `algo` - The hash algorithm used to address the blob.
+pub fn write_file_blob<P1: AsRef<Path>, P2: AsRef<Path>>(from: P1, to: P2, algo: HashAlgo) -> Result<String, std::io::Error> {
+    let contents = std::fs::read(&from)?;
+    let mut hasher = algo.hasher();
+    hasher.update(&contents);
+    let oid = to_hex(&hasher.finalize());
+    store_bytes_with_oid(to, algo, &oid, &contents)?;
+    Ok(oid)
+}
+
+/// Persists `from`'s content into the store at `to`, addressed by an already-known `oid` (e.g.
+/// from [`partial_hash`] when it already produced the true content hash), skipping a redundant
+/// re-hash of the file.
+pub fn store_blob_with_oid<P1: AsRef<Path>, P2: AsRef<Path>>(from: P1, to: P2, algo: HashAlgo, oid: &str) -> Result<(), std::io::Error> {
+    let contents = std::fs::read(&from)?;
+    store_bytes_with_oid(to, algo, oid, &contents)
+}
+
+fn store_bytes_with_oid<P: AsRef<Path>>(to: P, algo: HashAlgo, oid: &str, contents: &[u8]) -> Result<(), std::io::Error> {
+    let subfolder = &oid[..2];
+    let file_name = &oid[2..];
     let subfolder_path = to.as_ref().join(subfolder);
     if !subfolder_path.exists() {
         std::fs::create_dir_all(&subfolder_path)?;
     }
     let dst = subfolder_path.join(file_name);
-    if dst.exists() {
-        return Ok(());
+    let mut header_and_contents = Vec::with_capacity(1 + contents.len());
+    header_and_contents.push(algo.id());
+    header_and_contents.extend_from_slice(contents);
+    write_object_once(&dst, &header_and_contents)
+}
+
+/// Returns the process-wide gate gating concurrent writes to store object paths.
+///
+/// `create_tree`/`write_tree` run entries across a rayon thread pool, so two threads can race to
+/// write the same object path (e.g. two files with identical content). Racing on a
+/// check-then-act `exists()`/`write` pair can truncate or interleave a write; the gate below
+/// gives each path a single writer instead.
+fn object_write_gate() -> &'static Mutex<HashSet<PathBuf>> {
+    static GATE: OnceLock<Mutex<HashSet<PathBuf>>> = OnceLock::new();
+    GATE.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+/// Writes `contents` to `dst` at most once, even under concurrent callers racing on the same
+/// path. Later callers for a path that's already been written (by this process) are no-ops,
+/// mirroring the store's existing "first write wins" semantics.
+pub(crate) fn write_object_once(dst: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+    {
+        let mut written = object_write_gate().lock().unwrap();
+        if dst.exists() || !written.insert(dst.to_path_buf()) {
+            return Ok(());
+        }
     }
-    std::fs::copy(from, dst)?;
+    let mut file = File::create(dst)?;
+    file.write_all(contents)?;
     Ok(())
 }
 
@@ -61,19 +146,47 @@ mod tests {
     use std::path::PathBuf;
 
     #[test]
-    fn test_calculate_sha1() {
+    fn test_calculate_hash() {
         // Create a temporary file for testing
         let file_path = "test_file1.txt";
         let mut file = File::create(file_path).unwrap();
         file.write_all(b"hello world").unwrap();
-        // Calculate the SHA1 hash of the file
-        let hash = calculate_sha1(file_path).unwrap();
+        // Calculate the hash of the file
+        let hash = calculate_hash(file_path, HashAlgo::Sha1).unwrap();
         // Check that the hash is correct
         assert_eq!(hash, "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed");
         // Delete the temporary file
         std::fs::remove_file(file_path).unwrap();
     }
 
+    #[test]
+    fn test_partial_hash_is_full_hash_for_small_files() {
+        // A file smaller than the partial-hash block is entirely covered by the partial read, so
+        // the returned hash should be the file's true content hash (and reported as such).
+        let file_path = "test_file3.txt";
+        let mut file = File::create(file_path).unwrap();
+        file.write_all(b"hello world").unwrap();
+
+        let (partial, mode) = partial_hash(file_path, HashAlgo::Sha1).unwrap();
+        assert_eq!(mode, HashMode::Full);
+        assert_eq!(partial, calculate_hash(file_path, HashAlgo::Sha1).unwrap());
+
+        std::fs::remove_file(file_path).unwrap();
+    }
+
+    #[test]
+    fn test_partial_hash_is_partial_for_large_files() {
+        let file_path = "test_file5.txt";
+        let mut file = File::create(file_path).unwrap();
+        file.write_all(&vec![b'x'; PARTIAL_HASH_BLOCK_SIZE + 1]).unwrap();
+
+        let (partial, mode) = partial_hash(file_path, HashAlgo::Sha1).unwrap();
+        assert_eq!(mode, HashMode::Partial);
+        assert_ne!(partial, calculate_hash(file_path, HashAlgo::Sha1).unwrap());
+
+        std::fs::remove_file(file_path).unwrap();
+    }
+
     #[test]
     fn test_copy_file_to_dir() -> Result<(), std::io::Error> {
         let file_path = "test_file2.txt";
@@ -81,17 +194,19 @@ mod tests {
         file.write_all(b"hello world").unwrap();
         let output_dir = PathBuf::from("test_output");
         std::fs::create_dir(&output_dir)?;
-        write_file_blob(&file_path, &output_dir)?;
-        let hash = calculate_sha1(&file_path)?;
+        let hash = write_file_blob(file_path, &output_dir, HashAlgo::Sha1)?;
+        assert_eq!(hash, calculate_hash(file_path, HashAlgo::Sha1)?);
         let subfolder = &hash[..2];
         let file_name = &hash[2..];
         let file_path_in_output_dir = output_dir.join(subfolder).join(file_name);
         let mut output_file = File::open(&file_path_in_output_dir)?;
-        let mut contents = String::new();
-        output_file.read_to_string(&mut contents)?;
-        assert_eq!(contents, "hello world");
+        let mut stored = Vec::new();
+        output_file.read_to_end(&mut stored)?;
+        // First byte is the algorithm header; the rest is the original file content.
+        assert_eq!(stored[0], HashAlgo::Sha1.id());
+        assert_eq!(&stored[1..], b"hello world");
         // Delete the temporary file and output directory
-        std::fs::remove_file(&file_path)?;
+        std::fs::remove_file(file_path)?;
         std::fs::remove_dir_all(&output_dir)?;
         Ok(())
     }
@@ -0,0 +1,101 @@
+use crate::hash::HashAlgo;
+use crate::tree::{load_tree, EntryKind};
+use anyhow::Result;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum CheckoutError {
+    #[error("truncated blob object")]
+    TruncatedObject,
+    #[error("object's algorithm header ({0}) does not match its tree's declared algorithm ({1})")]
+    AlgoMismatch(u8, u8),
+}
+
+/// Reconstructs the directory tree addressed by `root_oid` under `dest`, reading objects from
+/// `store`. This is the read-side counterpart to `tree::write_tree`.
+pub fn checkout(root_oid: &str, store: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest)?;
+    let tree = load_tree(root_oid, store)?;
+    for entry in &tree.entries {
+        let dest_path = dest.join(&entry.name);
+        match entry.kind {
+            EntryKind::Tree => checkout(&entry.oid, store, &dest_path)?,
+            EntryKind::Blob => checkout_blob(&entry.oid, store, &dest_path, tree.algo)?,
+        }
+    }
+    Ok(())
+}
+
+/// Writes the blob addressed by `oid_hex` back to disk at `dest`, stripping the object's
+/// algorithm header byte after checking it matches `expected_algo` (the algorithm its owning
+/// tree was addressed with - every object in a tree is written with the same algorithm, so a
+/// mismatch means a corrupted object or a store mixing objects from incompatible runs).
+fn checkout_blob(oid_hex: &str, store: &Path, dest: &Path, expected_algo: HashAlgo) -> Result<()> {
+    let subfolder = &oid_hex[..2];
+    let file_name = &oid_hex[2..];
+    let data = std::fs::read(store.join(subfolder).join(file_name))?;
+    let (&algo_id, contents) = data.split_first().ok_or(CheckoutError::TruncatedObject)?;
+    if algo_id != expected_algo.id() {
+        return Err(CheckoutError::AlgoMismatch(algo_id, expected_algo.id()).into());
+    }
+    std::fs::write(dest, contents)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashAlgo;
+    use crate::tree::write_tree;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_checkout_round_trip() {
+        let src = PathBuf::from("checkout_test_src");
+        let store = PathBuf::from("checkout_test_store");
+        let dest = PathBuf::from("checkout_test_dest");
+        let subdir = src.join("subdir");
+        std::fs::create_dir_all(&subdir).unwrap();
+
+        File::create(src.join("top.txt")).unwrap().write_all(b"top level").unwrap();
+        File::create(subdir.join("nested.txt")).unwrap().write_all(b"nested content").unwrap();
+
+        let root_oid = write_tree(&src, &store, HashAlgo::Sha1).unwrap();
+        checkout(&root_oid, &store, &dest).unwrap();
+
+        assert_eq!(std::fs::read_to_string(dest.join("top.txt")).unwrap(), "top level");
+        assert_eq!(std::fs::read_to_string(dest.join("subdir").join("nested.txt")).unwrap(), "nested content");
+
+        std::fs::remove_dir_all(src).unwrap();
+        std::fs::remove_dir_all(store).unwrap();
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+
+    #[test]
+    fn test_checkout_rejects_blob_with_mismatched_algo_header() {
+        let src = PathBuf::from("checkout_test_algo_mismatch_src");
+        let store = PathBuf::from("checkout_test_algo_mismatch_store");
+        let dest = PathBuf::from("checkout_test_algo_mismatch_dest");
+        std::fs::create_dir_all(&src).unwrap();
+        File::create(src.join("file.txt")).unwrap().write_all(b"hello world").unwrap();
+
+        let root_oid = write_tree(&src, &store, HashAlgo::Sha1).unwrap();
+        let tree = load_tree(&root_oid, &store).unwrap();
+        let blob_oid = &tree.entries[0].oid;
+        let object_path = store.join(&blob_oid[..2]).join(&blob_oid[2..]);
+
+        // Flip the stored blob's algorithm header so it no longer matches the tree's.
+        let mut data = std::fs::read(&object_path).unwrap();
+        data[0] = HashAlgo::Blake3.id();
+        std::fs::write(&object_path, data).unwrap();
+
+        assert!(checkout(&root_oid, &store, &dest).is_err());
+
+        std::fs::remove_dir_all(src).unwrap();
+        std::fs::remove_dir_all(store).unwrap();
+        std::fs::remove_dir_all(dest).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+use crate::tree::{load_tree, EntryKind};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// A single difference between two tree snapshots, keyed by the entry's path relative to the
+/// snapshot root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Modified { path: String, old_oid: String, new_oid: String },
+    Renamed { from: String, to: String },
+}
+
+/// Compares two snapshot roots already present in `store` and returns the set of changes between
+/// them.
+///
+/// Both trees are loaded and merge-walked name-by-name (entries are stored sorted by name): a
+/// name present on only one side is an `Added`/`Removed`; for names present on both sides, equal
+/// oids prune the whole subtree (no recursion), a `Tree` oid mismatch recurses into the subtree,
+/// and a `Blob`<->`Tree` kind change is reported as a removal plus an addition. A final pass pairs
+/// up `Removed`/`Added` blobs that share an oid into `Renamed` changes.
+pub fn diff_trees(old_root: &str, new_root: &str, store: &Path) -> Result<Vec<Change>> {
+    let mut changes = Vec::new();
+    let mut removed_blobs = Vec::new();
+    let mut added_blobs = Vec::new();
+    diff_tree_nodes(old_root, new_root, "", store, &mut changes, &mut removed_blobs, &mut added_blobs)?;
+    Ok(detect_renames(changes, removed_blobs, added_blobs))
+}
+
+fn diff_tree_nodes(
+    old_oid: &str,
+    new_oid: &str,
+    prefix: &str,
+    store: &Path,
+    changes: &mut Vec<Change>,
+    removed_blobs: &mut Vec<(String, String)>,
+    added_blobs: &mut Vec<(String, String)>,
+) -> Result<()> {
+    if old_oid == new_oid {
+        return Ok(());
+    }
+
+    let old_tree = load_tree(old_oid, store)?;
+    let new_tree = load_tree(new_oid, store)?;
+
+    let mut old_iter = old_tree.entries.iter().peekable();
+    let mut new_iter = new_tree.entries.iter().peekable();
+
+    loop {
+        match (old_iter.peek(), new_iter.peek()) {
+            (None, None) => break,
+            (Some(old_entry), None) => {
+                let path = join_path(prefix, &old_entry.name);
+                record_removed(changes, removed_blobs, old_entry.kind, path, &old_entry.oid);
+                old_iter.next();
+            }
+            (None, Some(new_entry)) => {
+                let path = join_path(prefix, &new_entry.name);
+                record_added(changes, added_blobs, new_entry.kind, path, &new_entry.oid);
+                new_iter.next();
+            }
+            (Some(old_entry), Some(new_entry)) => match old_entry.name.cmp(&new_entry.name) {
+                std::cmp::Ordering::Less => {
+                    let path = join_path(prefix, &old_entry.name);
+                    record_removed(changes, removed_blobs, old_entry.kind, path, &old_entry.oid);
+                    old_iter.next();
+                }
+                std::cmp::Ordering::Greater => {
+                    let path = join_path(prefix, &new_entry.name);
+                    record_added(changes, added_blobs, new_entry.kind, path, &new_entry.oid);
+                    new_iter.next();
+                }
+                std::cmp::Ordering::Equal => {
+                    let path = join_path(prefix, &old_entry.name);
+                    match (old_entry.kind, new_entry.kind) {
+                        (EntryKind::Tree, EntryKind::Tree) => {
+                            if old_entry.oid != new_entry.oid {
+                                diff_tree_nodes(&old_entry.oid, &new_entry.oid, &path, store, changes, removed_blobs, added_blobs)?;
+                            }
+                        }
+                        (EntryKind::Blob, EntryKind::Blob) => {
+                            if old_entry.oid != new_entry.oid {
+                                changes.push(Change::Modified {
+                                    path,
+                                    old_oid: old_entry.oid.clone(),
+                                    new_oid: new_entry.oid.clone(),
+                                });
+                            }
+                        }
+                        _ => {
+                            // A blob<->tree type change is reported as a removal plus an addition.
+                            changes.push(Change::Removed(path.clone()));
+                            changes.push(Change::Added(path));
+                        }
+                    }
+                    old_iter.next();
+                    new_iter.next();
+                }
+            },
+        }
+    }
+    Ok(())
+}
+
+fn record_removed(changes: &mut Vec<Change>, removed_blobs: &mut Vec<(String, String)>, kind: EntryKind, path: String, oid: &str) {
+    if kind == EntryKind::Blob {
+        removed_blobs.push((path.clone(), oid.to_string()));
+    }
+    changes.push(Change::Removed(path));
+}
+
+fn record_added(changes: &mut Vec<Change>, added_blobs: &mut Vec<(String, String)>, kind: EntryKind, path: String, oid: &str) {
+    if kind == EntryKind::Blob {
+        added_blobs.push((path.clone(), oid.to_string()));
+    }
+    changes.push(Change::Added(path));
+}
+
+fn join_path(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Pairs up `Removed`/`Added` blob changes that share an oid into `Renamed` changes.
+fn detect_renames(mut changes: Vec<Change>, removed_blobs: Vec<(String, String)>, added_blobs: Vec<(String, String)>) -> Vec<Change> {
+    let mut added_by_oid: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (path, oid) in &added_blobs {
+        added_by_oid.entry(oid.as_str()).or_default().push(path.as_str());
+    }
+
+    let mut used_added: HashSet<&str> = HashSet::new();
+    let mut renamed_pairs = Vec::new();
+    for (removed_path, oid) in &removed_blobs {
+        if let Some(candidates) = added_by_oid.get(oid.as_str()) {
+            if let Some(&to) = candidates.iter().find(|p| !used_added.contains(*p)) {
+                used_added.insert(to);
+                renamed_pairs.push((removed_path.clone(), to.to_string()));
+            }
+        }
+    }
+    if renamed_pairs.is_empty() {
+        return changes;
+    }
+
+    let renamed_froms: HashSet<&str> = renamed_pairs.iter().map(|(from, _)| from.as_str()).collect();
+    let renamed_tos: HashSet<&str> = renamed_pairs.iter().map(|(_, to)| to.as_str()).collect();
+    changes.retain(|c| match c {
+        Change::Removed(path) => !renamed_froms.contains(path.as_str()),
+        Change::Added(path) => !renamed_tos.contains(path.as_str()),
+        _ => true,
+    });
+    changes.extend(renamed_pairs.into_iter().map(|(from, to)| Change::Renamed { from, to }));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashAlgo;
+    use crate::tree::write_tree;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_diff_trees() {
+        let root = PathBuf::from("diff_test_data");
+        let store = PathBuf::from("diff_test_store");
+        let old_dir = root.join("old");
+        let new_dir = root.join("new");
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::create_dir_all(&new_dir).unwrap();
+
+        File::create(old_dir.join("unchanged.txt")).unwrap().write_all(b"same").unwrap();
+        File::create(old_dir.join("removed.txt")).unwrap().write_all(b"bye").unwrap();
+
+        File::create(new_dir.join("unchanged.txt")).unwrap().write_all(b"same").unwrap();
+        File::create(new_dir.join("added.txt")).unwrap().write_all(b"hi").unwrap();
+
+        let old_root = write_tree(&old_dir, &store, HashAlgo::Sha1).unwrap();
+        let new_root = write_tree(&new_dir, &store, HashAlgo::Sha1).unwrap();
+
+        let mut changes = diff_trees(&old_root, &new_root, &store).unwrap();
+        changes.sort_by_key(|c| match c {
+            Change::Added(path) | Change::Removed(path) => path.clone(),
+            Change::Modified { path, .. } => path.clone(),
+            Change::Renamed { from, .. } => from.clone(),
+        });
+
+        assert_eq!(
+            changes,
+            vec![
+                Change::Added("added.txt".to_string()),
+                Change::Removed("removed.txt".to_string()),
+            ]
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+        std::fs::remove_dir_all(store).unwrap();
+    }
+}
@@ -0,0 +1,119 @@
+use anyhow::Result;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+/// Hash algorithm used to address objects in the content-addressable store.
+///
+/// The algorithm is recorded in every object's header (see [`HashAlgo::id`]) so that a reader
+/// can pick the right verifier without being told out of band which algorithm produced a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HashAlgo {
+    /// SHA1. Collision-prone for a content-addressable store; kept for compatibility.
+    #[default]
+    Sha1,
+    /// BLAKE3, a fast cryptographic hash.
+    Blake3,
+    /// xxHash3, a very fast non-cryptographic hash for trusted/dedup use cases.
+    Xxh3,
+}
+
+impl HashAlgo {
+    /// The 1-byte id persisted in an object header for this algorithm.
+    pub fn id(&self) -> u8 {
+        match self {
+            HashAlgo::Sha1 => 0,
+            HashAlgo::Blake3 => 1,
+            HashAlgo::Xxh3 => 2,
+        }
+    }
+
+    /// Recovers a `HashAlgo` from an object header byte.
+    pub fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(HashAlgo::Sha1),
+            1 => Ok(HashAlgo::Blake3),
+            2 => Ok(HashAlgo::Xxh3),
+            other => Err(HashError::UnknownAlgoId(other).into()),
+        }
+    }
+
+    /// Creates a fresh streaming hasher for this algorithm.
+    pub fn hasher(&self) -> Box<dyn Hasher> {
+        match self {
+            HashAlgo::Sha1 => Box::new(Sha1::new()),
+            HashAlgo::Blake3 => Box::new(blake3::Hasher::new()),
+            HashAlgo::Xxh3 => Box::new(Xxh3Hasher::default()),
+        }
+    }
+
+    /// The raw digest length, in bytes, produced by this algorithm.
+    pub fn digest_len(&self) -> usize {
+        match self {
+            HashAlgo::Sha1 => 20,
+            HashAlgo::Blake3 => 32,
+            HashAlgo::Xxh3 => 8,
+        }
+    }
+}
+
+/// Abstracts a streaming hash computation so callers don't need to depend on a specific hashing
+/// crate directly.
+pub trait Hasher {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self: Box<Self>) -> Vec<u8>;
+}
+
+impl Hasher for Sha1 {
+    fn update(&mut self, data: &[u8]) {
+        Digest::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        Digest::finalize(*self).to_vec()
+    }
+}
+
+impl Hasher for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        blake3::Hasher::finalize(&self).as_bytes().to_vec()
+    }
+}
+
+#[derive(Default)]
+struct Xxh3Hasher(xxhash_rust::xxh3::Xxh3);
+
+impl Hasher for Xxh3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> Vec<u8> {
+        self.0.digest().to_be_bytes().to_vec()
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum HashError {
+    #[error("unknown hash algorithm id: {0}")]
+    UnknownAlgoId(u8),
+}
+
+/// Whether a blob was addressed by a cheap prefix hash or its full content hash.
+///
+/// See [`crate::blob::partial_hash`]: a file no larger than one block is fully covered by the
+/// prefix read, so the expensive full-content pass can be skipped for it; a larger file always
+/// gets a full-content pass, however distinct its first block is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashMode {
+    Partial,
+    Full,
+}
+
+/// Hex-encodes raw hash bytes (lowercase), e.g. for use as an object id or path component.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
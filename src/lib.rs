@@ -0,0 +1,6 @@
+pub mod blob;
+pub mod checkout;
+pub mod diff;
+pub mod hash;
+pub mod pack;
+pub mod tree;
@@ -0,0 +1,172 @@
+use crate::hash::{to_hex, HashAlgo};
+use anyhow::Result;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use tar::{Archive, Builder, Header};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PackError {
+    #[error("truncated object in archive entry {0}")]
+    TruncatedObject(String),
+    #[error("archive entry name {0:?} is not a valid oid")]
+    InvalidEntryName(std::path::PathBuf),
+    #[error("object {name} failed hash verification: expected {name}, got {actual}")]
+    HashMismatch { name: String, actual: String },
+}
+
+/// Serializes every loose object in `store` into a tar archive written to `out`, one entry per
+/// object named by its full oid (`subfolder` + `file_name` concatenated).
+pub fn pack_store(store: &Path, out: impl Write) -> Result<()> {
+    let mut builder = Builder::new(out);
+    for subfolder in fs::read_dir(store)? {
+        let subfolder = subfolder?;
+        if !subfolder.path().is_dir() {
+            continue;
+        }
+        let prefix = subfolder.file_name().into_string().unwrap();
+        for object in fs::read_dir(subfolder.path())? {
+            let object = object?;
+            let suffix = object.file_name().into_string().unwrap();
+            let oid = format!("{}{}", prefix, suffix);
+            let data = fs::read(object.path())?;
+
+            let mut header = Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, &oid, data.as_slice())?;
+        }
+    }
+    builder.finish()?;
+    Ok(())
+}
+
+/// Restores objects from a tar archive produced by [`pack_store`] into the loose object store at
+/// `store`, skipping any object that already exists there. Each object's content is re-hashed with
+/// the algorithm recorded in its header byte and checked against its entry name before being
+/// written, so a corrupted or mismatched archive entry is rejected rather than silently imported.
+pub fn unpack_store(input: impl Read, store: &Path) -> Result<()> {
+    let mut archive = Archive::new(input);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let oid = entry_path
+            .to_str()
+            .map(str::to_string)
+            .ok_or_else(|| PackError::InvalidEntryName(entry_path.clone()))?;
+        if oid.len() < 2 {
+            return Err(PackError::InvalidEntryName(entry_path).into());
+        }
+
+        let subfolder = &oid[..2];
+        let file_name = &oid[2..];
+        let dst_dir = store.join(subfolder);
+        let dst = dst_dir.join(file_name);
+        if dst.exists() {
+            continue;
+        }
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+
+        let (&algo_id, contents) = data.split_first().ok_or_else(|| PackError::TruncatedObject(oid.clone()))?;
+        let algo = HashAlgo::from_id(algo_id)?;
+        if oid.len() != algo.digest_len() * 2 {
+            return Err(PackError::InvalidEntryName(entry_path).into());
+        }
+        let mut hasher = algo.hasher();
+        hasher.update(contents);
+        let actual = to_hex(&hasher.finalize());
+        if actual != oid {
+            return Err(PackError::HashMismatch { name: oid, actual }.into());
+        }
+
+        fs::create_dir_all(&dst_dir)?;
+        fs::write(dst, data)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::HashAlgo;
+    use crate::tree::write_tree;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_pack_unpack_round_trip() {
+        let dir = PathBuf::from("pack_test_data");
+        let store = PathBuf::from("pack_test_store");
+        let restored = PathBuf::from("pack_test_restored");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        File::create(dir.join("file1.txt")).unwrap().write_all(b"hello world").unwrap();
+        File::create(dir.join("file2.txt")).unwrap().write_all(b"goodbye world").unwrap();
+
+        write_tree(&dir, &store, HashAlgo::Sha1).unwrap();
+
+        let mut archive = Vec::new();
+        pack_store(&store, &mut archive).unwrap();
+        unpack_store(archive.as_slice(), &restored).unwrap();
+
+        let mut original_objects = collect_object_names(&store);
+        let mut restored_objects = collect_object_names(&restored);
+        original_objects.sort();
+        restored_objects.sort();
+        assert_eq!(original_objects, restored_objects);
+
+        for name in &original_objects {
+            let subfolder = &name[..2];
+            let file_name = &name[2..];
+            let original = fs::read(store.join(subfolder).join(file_name)).unwrap();
+            let round_tripped = fs::read(restored.join(subfolder).join(file_name)).unwrap();
+            assert_eq!(original, round_tripped);
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(store).unwrap();
+        std::fs::remove_dir_all(restored).unwrap();
+    }
+
+    #[test]
+    fn test_unpack_store_rejects_short_entry_name_instead_of_panicking() {
+        let restored = PathBuf::from("pack_test_short_name_restored");
+
+        let mut archive = Vec::new();
+        {
+            let mut builder = Builder::new(&mut archive);
+            let mut header = Header::new_gnu();
+            header.set_size(1);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, "a", &b"x"[..]).unwrap();
+            builder.finish().unwrap();
+        }
+
+        let result = unpack_store(archive.as_slice(), &restored);
+        assert!(result.is_err());
+        assert!(!restored.exists());
+    }
+
+    fn collect_object_names(store: &Path) -> Vec<String> {
+        let mut names = Vec::new();
+        for subfolder in fs::read_dir(store).unwrap() {
+            let subfolder = subfolder.unwrap();
+            if !subfolder.path().is_dir() {
+                continue;
+            }
+            let prefix = subfolder.file_name().into_string().unwrap();
+            for object in fs::read_dir(subfolder.path()).unwrap() {
+                let object = object.unwrap();
+                let suffix = object.file_name().into_string().unwrap();
+                names.push(format!("{}{}", prefix, suffix));
+            }
+        }
+        names
+    }
+}
@@ -1,28 +1,21 @@
 use crate::blob;
-use sha1::{Digest, Sha1};
+use crate::hash::{to_hex, HashAlgo, HashMode};
+use rayon::prelude::*;
 use std::fs;
+use std::fs::DirEntry;
 use std::path::Path;
 use anyhow::Result;
 use thiserror::Error;
 
 #[derive(Debug)]
 enum EntryId {
-    /// The SHA1 hash of the file
+    /// The content hash of the file
     Blob(String),
-    /// The SHA1 hash of the directory
+    /// The content hash of the directory
     Tree(String),
 }
 
 impl EntryId {
-    /// Compares the type of two `EntryId`s.
-    #[inline]
-    fn typeOrder(a: &Entry, b: &Entry) -> std::cmp::Ordering {
-        match (&a.oid, &b.oid) {
-            (EntryId::Blob(_), EntryId::Tree(_)) => std::cmp::Ordering::Less,
-            (EntryId::Tree(_), EntryId::Blob(_)) => std::cmp::Ordering::Greater,
-            _ => std::cmp::Ordering::Equal,
-        }
-    }
     fn get_id(&self) -> &str {
         match self {
             EntryId::Blob(sha1) => sha1,
@@ -40,10 +33,14 @@ struct Entry {
 }
 
 impl Entry {
-    /// get bytes with type(1) + length(2) + oid(20) + name
+    /// get bytes with type(1) + length(2) + oid(raw, hash-length-dependent) + name, where
+    /// `length` is the *total* serialized size of this entry (so a reader can skip/slice entries
+    /// without re-deriving it), and the oid/name split is recovered from the algorithm's known
+    /// digest length.
     fn bytes(&self) -> Result<Vec<u8>> {
         let oid = self.oid.get_id();
-        let length = 1 + 2 + oid.len() + self.name.len();
+        let raw_oid = hex_to_bytes(oid)?;
+        let length = 1 + 2 + raw_oid.len() + self.name.len();
         let mut bytes = Vec::with_capacity(length);
         // 类型1字节
         match &self.oid {
@@ -53,7 +50,7 @@ impl Entry {
         // 长度2字节
         bytes.extend(&(length as u16).to_be_bytes());
         // oid
-        bytes.extend(hex_to_bytes(oid)?);
+        bytes.extend(raw_oid);
         // 文件名
         bytes.extend(self.name.bytes());
         Ok(bytes)
@@ -66,16 +63,10 @@ struct Tree {
 }
 
 impl Tree {
+    /// Sorts entries by name, so two trees' entry lists can be merge-walked name-by-name (e.g.
+    /// by the diff engine) in O(n) instead of needing a lookup per entry.
     fn sort_entries(&mut self) {
-        self.entries.sort_by(|a, b| {
-            // 首先按类型排序，文件在前，目录在后
-            let type_order = EntryId::typeOrder(a, b);
-            if type_order != std::cmp::Ordering::Equal {
-                type_order
-            } else {
-                a.oid.get_id().cmp(&b.oid.get_id())
-            }
-        });
+        self.entries.sort_by(|a, b| a.name.cmp(&b.name));
     }
 
     pub fn bytes(&self) -> Result<Vec<u8>> {
@@ -90,17 +81,18 @@ impl Tree {
         Ok(bytes)
     }
 
-    fn calculate_sha1(&mut self) -> Result<String> {
+    fn calculate_hash(&mut self, algo: HashAlgo) -> Result<String> {
         self.sort_entries();
-        let mut hasher = Sha1::new();
+        let mut hasher = algo.hasher();
         hasher.update(&self.bytes()?);
-        let hash = hasher.finalize();
-        Ok(format!("{:x}", hash))
+        Ok(crate::hash::to_hex(&hasher.finalize()))
     }
 }
 
-fn write_tree<P1, P2>(from: P1, to: P2) -> Result<()> where P1: AsRef<Path>, P2: AsRef<Path> {
-    let mut tree = create_tree(from, &mut |t, hash| -> Result<()> {
+/// Writes the directory tree rooted at `from` into the content-addressable store at `to`,
+/// addressing every object with `algo`. Returns the root tree's oid.
+pub fn write_tree<P1, P2>(from: P1, to: P2, algo: HashAlgo) -> Result<String> where P1: AsRef<Path>, P2: AsRef<Path> + Sync {
+    let completed = |t: &Tree, hash: &str| -> Result<()> {
         let subfolder = &hash[..2];
         let file_name = &hash[2..];
         let subfolder_path = to.as_ref().join(subfolder);
@@ -108,46 +100,174 @@ fn write_tree<P1, P2>(from: P1, to: P2) -> Result<()> where P1: AsRef<Path>, P2:
             std::fs::create_dir_all(&subfolder_path)?;
         }
         let dst = subfolder_path.join(file_name);
-        if dst.exists() {
-            return Ok(());
-        }
-        std::fs::write(dst, t.bytes()?)?;
-        // std::fs::copy(from, dst)?;
+        let mut contents = Vec::with_capacity(1 + t.bytes()?.len());
+        contents.push(algo.id());
+        contents.extend(t.bytes()?);
+        // Tree construction runs across a rayon pool (see `create_tree`), so two threads can race
+        // to persist the same tree object; `write_object_once` gives each path a single writer
+        // instead of a racy check-then-act.
+        blob::write_object_once(&dst, &contents)?;
         Ok(())
-    })?;
-    let sha1 = tree.calculate_sha1()?;
-    println!("{}", sha1);
-    Ok(())
+    };
+    let mut tree = create_tree(from, to.as_ref(), algo, &completed)?;
+    tree.calculate_hash(algo)
 }
 
-/// Creates a `Tree` object from the given path.
-fn create_tree<P, F>(path: P, compeleted: &mut F) -> Result<Tree>
+/// Creates a `Tree` object from the given path, addressing every entry using `algo`. Files are
+/// persisted into the store at `store` as blob objects; directories are persisted via
+/// `compeleted` once their own `Tree` object has been built.
+///
+/// Per-entry work (blob hashing, recursive subtree construction) runs across a rayon thread
+/// pool, so `compeleted` must be safe to call concurrently from multiple threads; entries are
+/// collected back into a single `Vec` and sorted by name afterwards, so the resulting `Tree` is
+/// deterministic regardless of the order entries finished in.
+fn create_tree<P1, P2, F>(path: P1, store: P2, algo: HashAlgo, compeleted: &F) -> Result<Tree>
 where
-    P: AsRef<Path>,
-    F: FnMut(&Tree, &str) -> Result<()>,
+    P1: AsRef<Path>,
+    P2: AsRef<Path>,
+    F: Fn(&Tree, &str) -> Result<()> + Sync,
 {
-    let mut entries = Vec::new();
-    for entry in fs::read_dir(path)? {
-        let entry = entry.unwrap();
-        let name = entry.file_name().into_string().unwrap();
-        let path = entry.path();
-        if path.is_dir() {
-            let mut tree = create_tree(&path, compeleted)?;
-            let sha1 = tree.calculate_sha1()?;
-            compeleted(&tree, &sha1)?;
-            let oid = EntryId::Tree(sha1);
-            entries.push(Entry { name, oid });
-        } else {
-            let oid = EntryId::Blob(blob::calculate_sha1(&path)?);
-            entries.push(Entry { name, oid });
-        }
-    }
+    let store = store.as_ref();
+    let dir_entries: Vec<_> = fs::read_dir(path)?.map(|entry| entry.unwrap()).collect();
+    let (dirs, files): (Vec<_>, Vec<_>) = dir_entries.into_iter().partition(|entry| entry.path().is_dir());
+
+    let mut entries = dirs
+        .into_par_iter()
+        .map(|entry| -> Result<Entry> {
+            let name = entry.file_name().into_string().unwrap();
+            let path = entry.path();
+            let mut tree = create_tree(&path, store, algo, compeleted)?;
+            let oid = tree.calculate_hash(algo)?;
+            compeleted(&tree, &oid)?;
+            Ok(Entry { name, oid: EntryId::Tree(oid) })
+        })
+        .collect::<Result<Vec<Entry>>>()?;
+    entries.extend(hash_and_store_files(&files, store, algo)?);
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
     let mut result = Tree { entries };
-    let sha1 = result.calculate_sha1()?;
-    compeleted(&result, &sha1)?;
+    let oid = result.calculate_hash(algo)?;
+    compeleted(&result, &oid)?;
     Ok(result)
 }
 
+/// Hashes and persists a batch of file entries using the two-phase scheme from
+/// `blob::partial_hash`: for each file, the cheap prefix hash is computed first, and a
+/// full-content pass over that same file runs only when the prefix hash does not cover it.
+/// Files are processed independently across the rayon pool, so one file's full-content pass may
+/// run before another file's prefix hash.
+///
+/// Whenever that comes back `HashMode::Full` the prefix hash already *is* the file's true content
+/// hash (it fit entirely in one partial-hash block), so the entry is stored directly with no
+/// further hashing - this is the common case this scheme is meant to speed up, and it's a real
+/// saving since `calculate_hash` would otherwise re-read and re-hash those same bytes.
+///
+/// A `HashMode::Partial` candidate is always promoted to a full content hash before storing,
+/// whether or not it happens to share its prefix with another entry in this same directory
+/// listing. The original design promotes only on an observed collision, but a single listing
+/// can't see collisions against objects already in the store from a separate run, so treating "no
+/// collision observed here" as license to skip the full-content pass would let that entry's oid
+/// drift from a true content hash - breaking the store's content-addressing guarantee for exactly
+/// the files large enough for a collision to matter. Promoting unconditionally keeps that
+/// guarantee; it does mean this scheme has nothing left to save for files bigger than one block,
+/// since persisting them requires a full read regardless.
+fn hash_and_store_files(entries: &[DirEntry], store: &Path, algo: HashAlgo) -> Result<Vec<Entry>> {
+    entries
+        .par_iter()
+        .map(|entry| -> Result<Entry> {
+            let name = entry.file_name().into_string().unwrap();
+            let path = entry.path();
+            let (partial_oid, mode) = blob::partial_hash(&path, algo)?;
+            let oid = match mode {
+                HashMode::Full => {
+                    blob::store_blob_with_oid(&path, store, algo, &partial_oid)?;
+                    partial_oid
+                }
+                HashMode::Partial => {
+                    let full_oid = blob::calculate_hash(&path, algo)?;
+                    blob::store_blob_with_oid(&path, store, algo, &full_oid)?;
+                    full_oid
+                }
+            };
+            Ok(Entry { name, oid: EntryId::Blob(oid) })
+        })
+        .collect()
+}
+
+/// Which kind of object a [`LoadedEntry`] addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EntryKind {
+    Blob,
+    Tree,
+}
+
+/// A single entry as read back from a stored tree object.
+#[derive(Debug, Clone)]
+pub(crate) struct LoadedEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    /// Hex-encoded oid of the referenced object.
+    pub oid: String,
+}
+
+/// A tree object as read back from the store: the algorithm it was addressed with, plus its
+/// entries in the on-disk (name) order.
+#[derive(Debug)]
+pub(crate) struct LoadedTree {
+    pub algo: HashAlgo,
+    pub entries: Vec<LoadedEntry>,
+}
+
+#[derive(Error, Debug)]
+pub enum TreeError {
+    #[error("truncated tree object")]
+    TruncatedObject,
+    #[error("unknown entry type byte: {0}")]
+    UnknownEntryType(u8),
+    #[error("entry name is not valid UTF-8")]
+    InvalidName,
+    #[error("oid {0:?} is too short to address an object")]
+    InvalidOid(String),
+}
+
+/// Loads and parses the tree object addressed by `oid_hex` from `store`, reversing
+/// [`Entry::bytes`]/[`Tree::bytes`].
+pub(crate) fn load_tree(oid_hex: &str, store: &Path) -> Result<LoadedTree> {
+    let subfolder = oid_hex.get(..2).ok_or_else(|| TreeError::InvalidOid(oid_hex.to_string()))?;
+    let file_name = oid_hex.get(2..).ok_or_else(|| TreeError::InvalidOid(oid_hex.to_string()))?;
+    let data = fs::read(store.join(subfolder).join(file_name))?;
+
+    let (&algo_id, rest) = data.split_first().ok_or(TreeError::TruncatedObject)?;
+    let algo = HashAlgo::from_id(algo_id)?;
+    let oid_len = algo.digest_len();
+
+    if rest.len() < 2 {
+        return Err(TreeError::TruncatedObject.into());
+    }
+    let entry_count = u16::from_be_bytes([rest[0], rest[1]]) as usize;
+    let mut cursor = &rest[2..];
+    let mut entries = Vec::with_capacity(entry_count);
+    for _ in 0..entry_count {
+        if cursor.len() < 3 {
+            return Err(TreeError::TruncatedObject.into());
+        }
+        let kind = match cursor[0] {
+            0 => EntryKind::Blob,
+            1 => EntryKind::Tree,
+            other => return Err(TreeError::UnknownEntryType(other).into()),
+        };
+        let entry_len = u16::from_be_bytes([cursor[1], cursor[2]]) as usize;
+        let body = cursor.get(3..entry_len).ok_or(TreeError::TruncatedObject)?;
+        if body.len() < oid_len {
+            return Err(TreeError::TruncatedObject.into());
+        }
+        let (oid_bytes, name_bytes) = body.split_at(oid_len);
+        let name = String::from_utf8(name_bytes.to_vec()).map_err(|_| TreeError::InvalidName)?;
+        entries.push(LoadedEntry { name, kind, oid: to_hex(oid_bytes) });
+        cursor = &cursor[entry_len..];
+    }
+    Ok(LoadedTree { algo, entries })
+}
+
 #[derive(Error, Debug)]
 pub enum HexError {
     #[error("Invalid hex digit at: {0}")]
@@ -160,7 +280,7 @@ pub fn hex_to_bytes(hex: &str) -> Result<Vec<u8>> {
             b'A'..=b'F' => Ok(c - b'A' + 10),
             b'a'..=b'f' => Ok(c - b'a' + 10),
             b'0'..=b'9' => Ok(c - b'0'),
-            _ => return Err(HexError::InvalidHexDigit(idx).into()),
+            _ => Err(HexError::InvalidHexDigit(idx).into()),
         }
     };
     hex.as_bytes()
@@ -195,6 +315,7 @@ mod tests {
         use std::io::Write;
 
         let dir = PathBuf::from("text_data");
+        let store = PathBuf::from("text_data_store");
         let subdir1 = dir.join("subdir1");
         let subdir2 = dir.join("subdir2");
         let file1 = dir.join("file1.txt");
@@ -213,14 +334,47 @@ mod tests {
         let mut f3 = File::create(&file3).unwrap();
         f3.write_all(b"foo bar").unwrap();
 
-        // let mut completed_count = 0;
-        let mut completed = |tree: &Tree, sha1: &str| -> Result<()> {
-            println!("tree: {:?}, sha1: {}", tree, sha1);
-            Ok(())  
+        let completed = |tree: &Tree, oid: &str| -> Result<()> {
+            println!("tree: {:?}, oid: {}", tree, oid);
+            Ok(())
         };
 
-        let tree = create_tree(&dir, &mut completed).unwrap();
+        let tree = create_tree(&dir, &store, HashAlgo::Sha1, &completed).unwrap();
         assert_eq!(tree.entries.len(), 3);
         std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(store).unwrap();
+    }
+
+    #[test]
+    fn test_load_tree_rejects_short_oid_instead_of_panicking() {
+        let store = PathBuf::from("text_data_load_tree_short_oid_store");
+        std::fs::create_dir_all(&store).unwrap();
+        let result = load_tree("a", &store);
+        assert!(result.is_err());
+        std::fs::remove_dir_all(store).unwrap();
+    }
+
+    #[test]
+    fn test_create_tree_oid_matches_full_hash_for_large_files() {
+        use std::fs::File;
+        use std::io::Write;
+
+        let dir = PathBuf::from("text_data_large_file");
+        let store = PathBuf::from("text_data_large_file_store");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let file = dir.join("big.bin");
+        let contents = vec![b'x'; blob::PARTIAL_HASH_BLOCK_SIZE + 1];
+        File::create(&file).unwrap().write_all(&contents).unwrap();
+
+        let completed = |_: &Tree, _: &str| -> Result<()> { Ok(()) };
+        let tree = create_tree(&dir, &store, HashAlgo::Sha1, &completed).unwrap();
+
+        // A file bigger than the partial-hash block must still end up addressed by its true
+        // content hash, not the cheaper prefix hash `partial_hash` returns for it.
+        assert_eq!(tree.entries[0].oid.get_id(), blob::calculate_hash(&file, HashAlgo::Sha1).unwrap());
+
+        std::fs::remove_dir_all(dir).unwrap();
+        std::fs::remove_dir_all(store).unwrap();
     }
 }